@@ -0,0 +1,116 @@
+use zeroize::Zeroize;
+
+use crypto::{aead::AeadAlgorithm, checksum, PublicKeyAlgorithm, SymmetricKeyAlgorithm};
+use errors::Result;
+use types::*;
+
+use super::plain_secret::{ct_eq, key_version_octet, PlainSecretParams};
+
+/// Secret key material that is protected by a passphrase.
+///
+/// `data` is the ciphertext produced by [`PlainSecretParams::encrypt`]; for a
+/// v5 key it is still wrapped in the four-octet length envelope that
+/// `encrypt` adds, so [`EncryptedSecretParams::decrypt`] strips that before
+/// touching the ciphertext itself.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptedSecretParams {
+    data: Vec<u8>,
+    iv_or_nonce: Vec<u8>,
+    alg: SymmetricKeyAlgorithm,
+    aead: Option<AeadAlgorithm>,
+    s2k: StringToKey,
+    s2k_usage: u8,
+}
+
+impl EncryptedSecretParams {
+    pub fn new(
+        data: Vec<u8>,
+        iv_or_nonce: Vec<u8>,
+        alg: SymmetricKeyAlgorithm,
+        aead: Option<AeadAlgorithm>,
+        s2k: StringToKey,
+        s2k_usage: u8,
+    ) -> Self {
+        EncryptedSecretParams {
+            data,
+            iv_or_nonce,
+            alg,
+            aead,
+            s2k,
+            s2k_usage,
+        }
+    }
+
+    /// Decrypts and authenticates the secret material, returning the plain
+    /// params on success.
+    ///
+    /// `packet_tag` and `public_params` must match what was passed to
+    /// [`PlainSecretParams::encrypt`]: for id 253 they are folded back into
+    /// the AEAD associated data, so a mismatch surfaces as a failed tag
+    /// check rather than silently accepted tampering.
+    pub fn decrypt(
+        &self,
+        passphrase: &str,
+        version: KeyVersion,
+        packet_tag: Tag,
+        pub_alg: PublicKeyAlgorithm,
+        public_params: &PublicParams,
+    ) -> Result<PlainSecretParams> {
+        let mut key = self.s2k.derive_key(passphrase, self.alg.key_size())?;
+
+        let ciphertext = if version == KeyVersion::V5 {
+            ensure!(self.data.len() >= 4, "v5 secret material truncated");
+            let (len_bytes, rest) = self.data.split_at(4);
+            let len = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]])
+                as usize;
+            ensure!(
+                rest.len() >= len,
+                "v5 secret material shorter than its declared length"
+            );
+            rest[..len].to_vec()
+        } else {
+            self.data.clone()
+        };
+
+        let mut plain = match self.s2k_usage {
+            254 => {
+                let mut data = ciphertext;
+                self.alg
+                    .decrypt_with_iv_regular(&key, &self.iv_or_nonce, &mut data)?;
+
+                ensure!(data.len() >= 20, "secret material missing checksum");
+                let split_at = data.len() - 20;
+                let stored_checksum = data.split_off(split_at);
+                let expected = checksum::calculate_sha1(&data);
+                ensure!(
+                    ct_eq(&stored_checksum, &expected),
+                    "secret key checksum mismatch"
+                );
+
+                data
+            }
+            253 => {
+                let aead_alg = self
+                    .aead
+                    .ok_or_else(|| format_err!("AEAD algorithm required for id 253"))?;
+
+                // must mirror the associated data assembled in `encrypt`
+                let mut ad = vec![packet_tag as u8, key_version_octet(version)];
+                public_params.to_writer(&mut ad)?;
+
+                let mut data = ciphertext;
+                self.alg
+                    .decrypt_with_aead(aead_alg, &key, &self.iv_or_nonce, &ad, &mut data)?;
+
+                data
+            }
+            _ => unimplemented_err!("id: {} not implemented yet", self.s2k_usage),
+        };
+
+        key.zeroize();
+        let res = PlainSecretParams::from_raw_slice(&plain, pub_alg);
+        plain.zeroize();
+
+        res
+    }
+}
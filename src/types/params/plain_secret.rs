@@ -1,31 +1,176 @@
 use std::{fmt, io};
 
-use num_bigint::BigUint;
+use elliptic_curve::sec1::ToEncodedPoint;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::Zero;
+use p256::SecretKey as P256SecretKey;
+use p384::SecretKey as P384SecretKey;
+use p521::SecretKey as P521SecretKey;
+use pkcs1::DecodeRsaPrivateKey;
+use pkcs8::DecodePrivateKey;
 use rand::{CryptoRng, Rng};
 use rsa::RSAPrivateKey;
+use sec1::DecodeEcPrivateKey;
+use zeroize::Zeroize;
 
-use crypto::{checksum, ECCCurve, PublicKeyAlgorithm, SymmetricKeyAlgorithm};
+use crypto::{aead::AeadAlgorithm, checksum, ECCCurve, PublicKeyAlgorithm, SymmetricKeyAlgorithm};
 use errors::Result;
 use ser::Serialize;
 use types::*;
 use util::{mpi, write_mpi, TeeWriter};
 
-#[derive(Clone, PartialEq, Eq)]
+/// A `Vec<u8>` that is guaranteed to be wiped on drop, so secret material
+/// does not linger on the heap or get duplicated by an accidental `Debug`/log.
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct Protected(Vec<u8>);
+
+impl AsRef<[u8]> for Protected {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Protected {
+    fn from(data: Vec<u8>) -> Self {
+        Protected(data)
+    }
+}
+
+impl From<&[u8]> for Protected {
+    fn from(data: &[u8]) -> Self {
+        Protected(data.to_vec())
+    }
+}
+
+impl fmt::Debug for Protected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Protected([..])")
+    }
+}
+
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
 pub enum PlainSecretParams {
     RSA {
-        d: Vec<u8>,
-        p: Vec<u8>,
-        q: Vec<u8>,
-        u: Vec<u8>,
+        d: Protected,
+        p: Protected,
+        q: Protected,
+        u: Protected,
     },
-    DSA(Vec<u8>),
-    ECDSA(Vec<u8>),
-    ECDH(Vec<u8>),
-    Elgamal(Vec<u8>),
-    EdDSA(Vec<u8>),
+    DSA(Protected),
+    ECDSA(Protected),
+    ECDH(Protected),
+    Elgamal(Protected),
+    EdDSA(Protected),
+}
+
+/// Left-pads `data` with zero bytes up to `len`.
+///
+/// MPI decoding strips leading zero bytes, so a secret scalar or point
+/// coordinate that happened to start with `0x00` comes back shorter than the
+/// curve's fixed field width. Curve libraries reject anything but the exact
+/// width, so this restores it before handing the bytes over.
+fn pad_to_len(data: &[u8], len: usize) -> Vec<u8> {
+    if data.len() >= len {
+        return data.to_vec();
+    }
+
+    let mut out = vec![0u8; len - data.len()];
+    out.extend_from_slice(data);
+    out
+}
+
+/// The actual OpenPGP version octet for `version`.
+///
+/// This must not be replaced with a bare `version as u8` cast: that only
+/// happens to match the real version number if `KeyVersion`'s discriminants
+/// are defined to line up with it, which isn't guaranteed by the type itself.
+/// AEAD associated data folds this octet in, so getting it wrong would make
+/// keys that still round-trip locally fail to verify against other OpenPGP
+/// implementations.
+pub(crate) fn key_version_octet(version: KeyVersion) -> u8 {
+    match version {
+        KeyVersion::V2 => 2,
+        KeyVersion::V3 => 3,
+        KeyVersion::V4 => 4,
+        KeyVersion::V5 => 5,
+    }
+}
+
+/// Computes `a⁻¹ mod m` via the extended Euclidean algorithm, or `None` if
+/// `a` and `m` are not coprime.
+fn mod_inverse(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    let m = BigInt::from_biguint(Sign::Plus, m.clone());
+    let mut t = BigInt::zero();
+    let mut new_t = BigInt::from(1);
+    let mut r = m.clone();
+    let mut new_r = BigInt::from_biguint(Sign::Plus, a.clone());
+
+    while !new_r.is_zero() {
+        let quotient = &r / &new_r;
+
+        let tmp_t = t - &quotient * &new_t;
+        t = new_t;
+        new_t = tmp_t;
+
+        let tmp_r = r - &quotient * &new_r;
+        r = new_r;
+        new_r = tmp_r;
+    }
+
+    if r != BigInt::from(1) {
+        return None;
+    }
+
+    if t.sign() == Sign::Minus {
+        t += &m;
+    }
+
+    t.to_biguint()
+}
+
+/// Extracts the raw 32 byte Ed25519 seed from a PKCS#8 DER document.
+fn ed25519_seed_from_pkcs8_der(der: &[u8]) -> Result<Vec<u8>> {
+    use pkcs8::der::Decode;
+
+    const ED25519_OID: pkcs8::ObjectIdentifier = pkcs8::ObjectIdentifier::new_unwrap("1.3.101.112");
+
+    let info =
+        pkcs8::PrivateKeyInfo::from_der(der).map_err(|_| format_err!("invalid PKCS#8 document"))?;
+    ensure_eq!(info.algorithm.oid, ED25519_OID, "not an Ed25519 key");
+
+    // the PKCS#8 `privateKey` OCTET STRING itself wraps a 32 byte OCTET
+    // STRING holding the raw seed (RFC 8410 section 7).
+    let inner = pkcs8::der::asn1::OctetStringRef::from_der(info.private_key)
+        .map_err(|_| format_err!("invalid Ed25519 private key encoding"))?;
+    let seed = inner.as_bytes();
+    ensure_eq!(seed.len(), 32, "invalid Ed25519 seed length");
+
+    Ok(seed.to_vec())
+}
+
+/// Compares two byte slices in constant time.
+///
+/// Length is checked up front in variable time — it is not secret, only the
+/// MPI contents are — so two byte strings that merely differ in length (e.g.
+/// trailing zero padding) are never reported equal. Equal-length slices are
+/// then compared by XORing every byte pair into a single accumulator, so no
+/// early exit leaks which byte first differed.
+pub(crate) fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+
+    diff == 0
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub enum PlainSecretParamsRef<'a> {
     RSA {
         d: &'a [u8],
@@ -41,8 +186,42 @@ pub enum PlainSecretParamsRef<'a> {
 }
 
 impl<'a> PlainSecretParamsRef<'a> {
-    pub fn from_slice(data: &'a [u8], alg: PublicKeyAlgorithm) -> Result<Self> {
-        let (_, repr) = parse_secret_params(data, alg)?;
+    /// Parses plain (unencrypted) secret key material.
+    ///
+    /// For a v5 key, `data` is expected to start with the four-octet length
+    /// of the secret MPI material, the same envelope that
+    /// [`PlainSecretParams::encrypt`] puts around v5 ciphertext; the MPI parser is bounded to exactly
+    /// that many bytes, so overrunning or underrunning it is an error rather
+    /// than silently reading into (or leaving behind) unrelated packet data.
+    pub fn from_slice(
+        data: &'a [u8],
+        version: KeyVersion,
+        alg: PublicKeyAlgorithm,
+    ) -> Result<Self> {
+        if version == KeyVersion::V5 {
+            ensure!(data.len() >= 4, "v5 secret material truncated");
+            let (len_bytes, rest) = data.split_at(4);
+            let len = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]])
+                as usize;
+            ensure!(
+                rest.len() >= len,
+                "v5 secret material shorter than its declared length"
+            );
+            let material = &rest[..len];
+
+            return Self::from_raw_slice(material, alg);
+        }
+
+        Self::from_raw_slice(data, alg)
+    }
+
+    /// Parses secret material that is not (or is no longer) wrapped in the
+    /// v5 four-octet length envelope, e.g. the plaintext left behind once
+    /// [`EncryptedSecretParams::decrypt`] has stripped and decrypted that
+    /// envelope's contents.
+    pub(crate) fn from_raw_slice(data: &'a [u8], alg: PublicKeyAlgorithm) -> Result<Self> {
+        let (leftover, repr) = parse_secret_params(data, alg)?;
+        ensure!(leftover.is_empty(), "secret material longer than expected");
 
         Ok(repr)
     }
@@ -50,16 +229,16 @@ impl<'a> PlainSecretParamsRef<'a> {
     pub fn to_owned(&self) -> PlainSecretParams {
         match self {
             PlainSecretParamsRef::RSA { d, p, q, u } => PlainSecretParams::RSA {
-                d: d.to_vec(),
-                p: p.to_vec(),
-                q: q.to_vec(),
-                u: u.to_vec(),
+                d: Protected::from(*d),
+                p: Protected::from(*p),
+                q: Protected::from(*q),
+                u: Protected::from(*u),
             },
-            PlainSecretParamsRef::DSA(v) => PlainSecretParams::DSA(v.to_vec()),
-            PlainSecretParamsRef::ECDSA(v) => PlainSecretParams::ECDSA(v.to_vec()),
-            PlainSecretParamsRef::ECDH(v) => PlainSecretParams::ECDH(v.to_vec()),
-            PlainSecretParamsRef::Elgamal(v) => PlainSecretParams::Elgamal(v.to_vec()),
-            PlainSecretParamsRef::EdDSA(v) => PlainSecretParams::EdDSA(v.to_vec()),
+            PlainSecretParamsRef::DSA(v) => PlainSecretParams::DSA(Protected::from(*v)),
+            PlainSecretParamsRef::ECDSA(v) => PlainSecretParams::ECDSA(Protected::from(*v)),
+            PlainSecretParamsRef::ECDH(v) => PlainSecretParams::ECDH(Protected::from(*v)),
+            PlainSecretParamsRef::Elgamal(v) => PlainSecretParams::Elgamal(Protected::from(*v)),
+            PlainSecretParamsRef::EdDSA(v) => PlainSecretParams::EdDSA(Protected::from(*v)),
         }
     }
 
@@ -135,6 +314,21 @@ impl<'a> PlainSecretParamsRef<'a> {
                         let mut secret = [0u8; 32];
                         secret.copy_from_slice(d);
 
+                        Ok(SecretKeyRepr::ECDH(ECDHSecretKey {
+                            oid: curve.oid(),
+                            hash: *hash,
+                            alg_sym: *alg_sym,
+                            secret: secret.to_vec(),
+                        }))
+                    }
+                    ECCCurve::P256 | ECCCurve::P384 | ECCCurve::P521 => {
+                        // restore the fixed-width scalar MPI decoding stripped
+                        let secret = pad_to_len(d, (curve.nbits() + 7) / 8);
+
+                        // `ECDHSecretKey.secret` carries a NIST scalar here, not
+                        // an X25519 key as it does for `Curve25519` above; the
+                        // distinct `oid` is what downstream ECDH key-wrap code
+                        // must branch on to tell the two representations apart.
                         Ok(SecretKeyRepr::ECDH(ECDHSecretKey {
                             oid: curve.oid(),
                             hash: *hash,
@@ -163,25 +357,201 @@ impl<'a> PlainSecretParamsRef<'a> {
                 },
                 _ => unreachable!("inconsistent key state"),
             },
-            PlainSecretParamsRef::DSA(_) => {
-                unimplemented_err!("DSA");
-            }
-            PlainSecretParamsRef::Elgamal(_) => {
-                unimplemented_err!("Elgamal");
-            }
-            PlainSecretParamsRef::ECDSA(_) => {
-                unimplemented_err!("ECDSA");
-            }
+            PlainSecretParamsRef::DSA(x) => match public_params {
+                PublicParams::DSA {
+                    ref p,
+                    ref q,
+                    ref g,
+                    ref y,
+                } => Ok(SecretKeyRepr::DSA(DSASecretKey {
+                    x: BigUint::from_bytes_be(x),
+                    p: BigUint::from_bytes_be(p),
+                    q: BigUint::from_bytes_be(q),
+                    g: BigUint::from_bytes_be(g),
+                    y: BigUint::from_bytes_be(y),
+                })),
+                _ => unreachable!("inconsistent key state"),
+            },
+            PlainSecretParamsRef::Elgamal(x) => match public_params {
+                PublicParams::Elgamal {
+                    ref p,
+                    ref g,
+                    ref y,
+                } => Ok(SecretKeyRepr::Elgamal(ElgamalSecretKey {
+                    x: BigUint::from_bytes_be(x),
+                    p: BigUint::from_bytes_be(p),
+                    g: BigUint::from_bytes_be(g),
+                    y: BigUint::from_bytes_be(y),
+                })),
+                _ => unreachable!("inconsistent key state"),
+            },
+            PlainSecretParamsRef::ECDSA(d) => match public_params {
+                PublicParams::ECDSA { ref curve, .. } => match *curve {
+                    ECCCurve::P256 => {
+                        let secret = pad_to_len(d, (curve.nbits() + 7) / 8);
+                        let key = P256SecretKey::from_bytes(&secret)
+                            .map_err(|_| format_err!("invalid P-256 secret key"))?;
+
+                        Ok(SecretKeyRepr::ECDSA(ECDSASecretKey::P256(key)))
+                    }
+                    ECCCurve::P384 => {
+                        let secret = pad_to_len(d, (curve.nbits() + 7) / 8);
+                        let key = P384SecretKey::from_bytes(&secret)
+                            .map_err(|_| format_err!("invalid P-384 secret key"))?;
+
+                        Ok(SecretKeyRepr::ECDSA(ECDSASecretKey::P384(key)))
+                    }
+                    ECCCurve::P521 => {
+                        let secret = pad_to_len(d, (curve.nbits() + 7) / 8);
+                        let key = P521SecretKey::from_bytes(&secret)
+                            .map_err(|_| format_err!("invalid P-521 secret key"))?;
+
+                        Ok(SecretKeyRepr::ECDSA(ECDSASecretKey::P521(key)))
+                    }
+                    _ => unsupported_err!("curve {:?} for ECDSA", curve.to_string()),
+                },
+                _ => unreachable!("inconsistent key state"),
+            },
         }
     }
 }
 
 impl PlainSecretParams {
-    pub fn from_slice(data: &[u8], alg: PublicKeyAlgorithm) -> Result<Self> {
-        let ref_params = PlainSecretParamsRef::from_slice(data, alg)?;
+    pub fn from_slice(data: &[u8], version: KeyVersion, alg: PublicKeyAlgorithm) -> Result<Self> {
+        let ref_params = PlainSecretParamsRef::from_slice(data, version, alg)?;
         Ok(ref_params.to_owned())
     }
 
+    /// See [`PlainSecretParamsRef::from_raw_slice`].
+    pub(crate) fn from_raw_slice(data: &[u8], alg: PublicKeyAlgorithm) -> Result<Self> {
+        let ref_params = PlainSecretParamsRef::from_raw_slice(data, alg)?;
+        Ok(ref_params.to_owned())
+    }
+
+    /// Imports an externally generated RSA, ECDSA, or EdDSA private key from
+    /// DER, producing params ready to be handed to [`PlainSecretParams::encrypt`].
+    ///
+    /// Mirrors how most toolchains read a key of unknown provenance: for each
+    /// key type in turn (RSA, then P-256, P-384, P-521), try the
+    /// self-describing PKCS#8 envelope first, then fall back to the older
+    /// algorithm-specific PKCS#1 (RSA) or SEC1 (EC) encoding; Ed25519 is
+    /// tried last, as PKCS#8 only.
+    pub fn from_der(der: &[u8]) -> Result<(Self, PublicParams)> {
+        if let Ok(key) = RSAPrivateKey::from_pkcs8_der(der) {
+            return Self::from_rsa_key(key);
+        }
+        if let Ok(key) = RSAPrivateKey::from_pkcs1_der(der) {
+            return Self::from_rsa_key(key);
+        }
+
+        if let Ok(key) = P256SecretKey::from_pkcs8_der(der) {
+            return Ok(Self::from_p256_key(key));
+        }
+        if let Ok(key) = P256SecretKey::from_sec1_der(der) {
+            return Ok(Self::from_p256_key(key));
+        }
+
+        if let Ok(key) = P384SecretKey::from_pkcs8_der(der) {
+            return Ok(Self::from_p384_key(key));
+        }
+        if let Ok(key) = P384SecretKey::from_sec1_der(der) {
+            return Ok(Self::from_p384_key(key));
+        }
+
+        if let Ok(key) = P521SecretKey::from_pkcs8_der(der) {
+            return Ok(Self::from_p521_key(key));
+        }
+        if let Ok(key) = P521SecretKey::from_sec1_der(der) {
+            return Ok(Self::from_p521_key(key));
+        }
+
+        if let Ok(seed) = ed25519_seed_from_pkcs8_der(der) {
+            return Self::from_ed25519_seed(&seed);
+        }
+
+        bail!("unrecognized private key encoding");
+    }
+
+    fn from_rsa_key(key: RSAPrivateKey) -> Result<(Self, PublicParams)> {
+        let primes = key.primes();
+        ensure_eq!(primes.len(), 2, "multi-prime RSA keys are not supported");
+
+        // RFC 4880 section 5.5.3 requires the stored primes to satisfy
+        // p < q, but PKCS#1/OpenSSL conventionally store the larger prime
+        // first — swap them if needed before computing u.
+        let (p, q) = if primes[0] < primes[1] {
+            (&primes[0], &primes[1])
+        } else {
+            (&primes[1], &primes[0])
+        };
+
+        // OpenPGP stores u = p⁻¹ mod q, the opposite convention from the
+        // q⁻¹ mod p CRT coefficient most PKCS#1 keys carry.
+        let u = mod_inverse(p, q).ok_or_else(|| format_err!("p and q are not coprime"))?;
+
+        let params = PlainSecretParams::RSA {
+            d: Protected::from(key.d().to_bytes_be()),
+            p: Protected::from(p.to_bytes_be()),
+            q: Protected::from(q.to_bytes_be()),
+            u: Protected::from(u.to_bytes_be()),
+        };
+        let public = PublicParams::RSA {
+            n: key.n().to_bytes_be(),
+            e: key.e().to_bytes_be(),
+        };
+
+        Ok((params, public))
+    }
+
+    fn from_p256_key(key: P256SecretKey) -> (Self, PublicParams) {
+        let public_point = key.public_key().to_encoded_point(false);
+        let params = PlainSecretParams::ECDSA(Protected::from(key.to_bytes().to_vec()));
+        let public = PublicParams::ECDSA {
+            curve: ECCCurve::P256,
+            p: public_point.as_bytes().to_vec(),
+        };
+
+        (params, public)
+    }
+
+    fn from_p384_key(key: P384SecretKey) -> (Self, PublicParams) {
+        let public_point = key.public_key().to_encoded_point(false);
+        let params = PlainSecretParams::ECDSA(Protected::from(key.to_bytes().to_vec()));
+        let public = PublicParams::ECDSA {
+            curve: ECCCurve::P384,
+            p: public_point.as_bytes().to_vec(),
+        };
+
+        (params, public)
+    }
+
+    fn from_p521_key(key: P521SecretKey) -> (Self, PublicParams) {
+        let public_point = key.public_key().to_encoded_point(false);
+        let params = PlainSecretParams::ECDSA(Protected::from(key.to_bytes().to_vec()));
+        let public = PublicParams::ECDSA {
+            curve: ECCCurve::P521,
+            p: public_point.as_bytes().to_vec(),
+        };
+
+        (params, public)
+    }
+
+    fn from_ed25519_seed(seed: &[u8]) -> Result<(Self, PublicParams)> {
+        ensure_eq!(seed.len(), 32, "invalid Ed25519 seed length");
+
+        let signing_key = ed25519_dalek::SecretKey::from_bytes(seed)
+            .map_err(|_| format_err!("invalid Ed25519 secret key"))?;
+        let public_key: ed25519_dalek::PublicKey = (&signing_key).into();
+
+        let params = PlainSecretParams::EdDSA(Protected::from(seed.to_vec()));
+        let public = PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q: public_key.to_bytes().to_vec(),
+        };
+
+        Ok((params, public))
+    }
+
     pub fn string_to_key_id(&self) -> u8 {
         self.as_ref().string_to_key_id()
     }
@@ -210,45 +580,133 @@ impl PlainSecretParams {
         }
     }
 
+    /// Encrypts this secret key material with the given passphrase.
+    ///
+    /// `id` is the S2K usage octet: `254` selects CFB encryption with a
+    /// trailing SHA-1 checksum, `253` selects the AEAD-protected format, in
+    /// which case `aead` must name the AEAD mode to use. `packet_tag` and
+    /// `public_params` are folded into the AEAD associated data so the
+    /// authentication tag covers the whole key, not just the secret part.
+    ///
+    /// For a v5 key, the resulting secret material is prefixed with a
+    /// four-octet count of its length, so that implementations which do not
+    /// recognize `alg`/`aead` can still skip over it.
     pub fn encrypt<R: CryptoRng + Rng>(
         self,
         rng: &mut R,
         passphrase: &str,
         alg: SymmetricKeyAlgorithm,
+        aead: Option<AeadAlgorithm>,
         s2k: StringToKey,
         version: KeyVersion,
         id: u8,
+        packet_tag: Tag,
+        public_params: &PublicParams,
     ) -> Result<EncryptedSecretParams> {
-        let key = s2k.derive_key(passphrase, alg.key_size())?;
-        let mut iv = vec![0u8; alg.block_size()];
-        rng.fill(&mut iv[..]);
-
-        let enc_data = match version {
+        match version {
             KeyVersion::V2 => unsupported_err!("Encryption for V2 keys is not available"),
             KeyVersion::V3 => unimplemented_err!("v3 encryption"),
-            KeyVersion::V4 => {
+            KeyVersion::V4 | KeyVersion::V5 => {}
+        }
+
+        let mut key = s2k.derive_key(passphrase, alg.key_size())?;
+
+        let (mut material, mut iv_or_nonce, aead_used) = match id {
+            254 => {
+                let mut iv = vec![0u8; alg.block_size()];
+                rng.fill(&mut iv[..]);
+
                 let mut data = Vec::new();
                 self.as_ref()
                     .to_writer_raw(&mut data)
                     .expect("preallocated vector");
-                match id {
-                    254 => {
-                        data.extend_from_slice(&self.checksum_sha1()[..]);
-                    }
-                    _ => unimplemented_err!("id: {} not implemented yet", id),
-                }
+                data.extend_from_slice(&self.checksum_sha1()[..]);
 
                 alg.encrypt_with_iv_regular(&key, &iv, &mut data)?;
 
-                data
+                (data, iv, None)
             }
-            KeyVersion::V5 => unimplemented_err!("v5 encryption"),
+            253 => {
+                let aead_alg =
+                    aead.ok_or_else(|| format_err!("AEAD algorithm required for id 253"))?;
+
+                let mut nonce = vec![0u8; aead_alg.nonce_len()];
+                rng.fill(&mut nonce[..]);
+
+                // integrity must cover the whole key, not just the secret part
+                let mut ad = vec![packet_tag as u8, key_version_octet(version)];
+                public_params.to_writer(&mut ad)?;
+
+                let mut data = Vec::new();
+                self.as_ref()
+                    .to_writer_raw(&mut data)
+                    .expect("preallocated vector");
+
+                alg.encrypt_with_aead(aead_alg, &key, &nonce, &ad, &mut data)?;
+
+                (data, nonce, Some(aead_alg))
+            }
+            _ => unimplemented_err!("id: {} not implemented yet", id),
         };
 
-        Ok(EncryptedSecretParams::new(enc_data, iv, alg, s2k, id))
+        if version == KeyVersion::V5 {
+            let mut framed = Vec::with_capacity(4 + material.len());
+            framed.extend_from_slice(&(material.len() as u32).to_be_bytes());
+            framed.extend_from_slice(&material);
+            material.zeroize();
+            material = framed;
+        }
+
+        let res =
+            EncryptedSecretParams::new(material, iv_or_nonce.clone(), alg, aead_used, s2k, id);
+
+        key.zeroize();
+        iv_or_nonce.zeroize();
+
+        Ok(res)
+    }
+}
+
+impl PartialEq for Protected {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Protected {}
+
+impl PartialEq for PlainSecretParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ref() == other.as_ref()
+    }
+}
+
+impl Eq for PlainSecretParams {}
+
+impl<'a> PartialEq for PlainSecretParamsRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                PlainSecretParamsRef::RSA { d, p, q, u },
+                PlainSecretParamsRef::RSA {
+                    d: od,
+                    p: op,
+                    q: oq,
+                    u: ou,
+                },
+            ) => ct_eq(d, od) && ct_eq(p, op) && ct_eq(q, oq) && ct_eq(u, ou),
+            (PlainSecretParamsRef::DSA(a), PlainSecretParamsRef::DSA(b)) => ct_eq(a, b),
+            (PlainSecretParamsRef::ECDSA(a), PlainSecretParamsRef::ECDSA(b)) => ct_eq(a, b),
+            (PlainSecretParamsRef::ECDH(a), PlainSecretParamsRef::ECDH(b)) => ct_eq(a, b),
+            (PlainSecretParamsRef::Elgamal(a), PlainSecretParamsRef::Elgamal(b)) => ct_eq(a, b),
+            (PlainSecretParamsRef::EdDSA(a), PlainSecretParamsRef::EdDSA(b)) => ct_eq(a, b),
+            _ => false,
+        }
     }
 }
 
+impl<'a> Eq for PlainSecretParamsRef<'a> {}
+
 impl Serialize for PlainSecretParams {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         self.as_ref().to_writer(writer)
@@ -309,3 +767,60 @@ named!(rsa_secret_params<PlainSecretParamsRef>, do_parse!(
     >> u: mpi
     >> (PlainSecretParamsRef::RSA { d, p, q, u })
 ));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pad_to_len_leaves_long_enough_data_untouched() {
+        assert_eq!(pad_to_len(&[1, 2, 3], 2), vec![1, 2, 3]);
+        assert_eq!(pad_to_len(&[1, 2, 3], 3), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn pad_to_len_left_pads_short_data_with_zeros() {
+        assert_eq!(pad_to_len(&[1, 2, 3], 5), vec![0, 0, 1, 2, 3]);
+        assert_eq!(pad_to_len(&[], 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn ct_eq_detects_equal_and_differing_slices() {
+        assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+        // differing lengths must never be considered equal, even when the
+        // longer side only adds zero bytes.
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 3, 0]));
+        assert!(!ct_eq(&[], &[0]));
+    }
+
+    #[test]
+    fn mod_inverse_computes_known_values() {
+        let a = BigUint::from(3u32);
+        let m = BigUint::from(11u32);
+        // 3 * 4 = 12 = 1 mod 11
+        assert_eq!(mod_inverse(&a, &m), Some(BigUint::from(4u32)));
+    }
+
+    #[test]
+    fn mod_inverse_returns_none_for_non_coprime_inputs() {
+        let a = BigUint::from(4u32);
+        let m = BigUint::from(8u32);
+        assert_eq!(mod_inverse(&a, &m), None);
+    }
+
+    #[test]
+    fn from_slice_rejects_truncated_v5_length_prefix() {
+        let err =
+            PlainSecretParamsRef::from_slice(&[0, 0, 1], KeyVersion::V5, PublicKeyAlgorithm::DSA);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn from_slice_rejects_v5_length_longer_than_available_data() {
+        // declares 10 bytes of secret material but only provides 2
+        let data = [0, 0, 0, 10, 1, 2];
+        let err = PlainSecretParamsRef::from_slice(&data, KeyVersion::V5, PublicKeyAlgorithm::DSA);
+        assert!(err.is_err());
+    }
+}